@@ -0,0 +1,66 @@
+use crate::ecdsa::ECDSA;
+use crate::utils::{EllipticCurve, Point};
+use num_bigint::BigUint;
+
+fn hex(s: &str) -> BigUint {
+    BigUint::parse_bytes(s.as_bytes(), 16).expect("invalid hex constant")
+}
+
+impl EllipticCurve {
+    /// secp256k1: y^2 = x^3 + 7 over F_p, the curve used by Bitcoin and Ethereum.
+    pub fn secp256k1() -> Self {
+        EllipticCurve {
+            a: BigUint::from(0u32),
+            b: BigUint::from(7u32),
+            p: hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F"),
+        }
+    }
+
+    /// NIST P-256 (secp256r1): y^2 = x^3 - 3x + b over F_p.
+    pub fn nist_p256() -> Self {
+        EllipticCurve {
+            a: hex("FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFC"),
+            b: hex("5AC635D8AA3A93E7B3EBBD55769886BC651D06B0CC53B0F63BCE3C3E27D2604B"),
+            p: hex("FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFF"),
+        }
+    }
+}
+
+impl ECDSA {
+    /// secp256k1 with the standard generator `G` and group order `q`.
+    pub fn secp256k1() -> Self {
+        let g = Point::Coor(
+            hex("79BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+            hex("483ADA7726A3C4655DA4FBFC0E1108A8FD17B448A68554199C47D08FFB10D4B8"),
+        );
+        let q = hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141");
+        ECDSA::new(EllipticCurve::secp256k1(), g, q)
+    }
+
+    /// NIST P-256 with the standard generator `G` and group order `q`.
+    pub fn nist_p256() -> Self {
+        let g = Point::Coor(
+            hex("6B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296"),
+            hex("4FE342E2FE1A7F9B8EE7EB4A7C0F9E162BCE33576B315ECECBB6406837BF51F5"),
+        );
+        let q = hex("FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551");
+        ECDSA::new(EllipticCurve::nist_p256(), g, q)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_secp256k1_generator_on_curve() {
+        let ecdsa = ECDSA::secp256k1();
+        let pub_key = ecdsa.generate_pub_key(&BigUint::from(1u32));
+        match pub_key {
+            Point::Coor(x, _) => {
+                assert_eq!(x, hex("79BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"))
+            }
+            Point::Identity => panic!("1 * G should not be the identity"),
+        }
+    }
+}
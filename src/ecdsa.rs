@@ -1,7 +1,10 @@
-use crate::utils::{EllipticCurve, FiniteField, Point};
+use crate::utils::{EcError, EllipticCurve, FiniteField, Point};
+use hmac::{Hmac, Mac};
 use num_bigint::{BigUint, RandBigInt};
-use rand::{self, Rng};
-use sha256::{digest, try_digest};
+use sha2::Sha256;
+use sha256::digest;
+
+type HmacSha256 = Hmac<Sha256>;
 
 pub struct ECDSA {
     ec: EllipticCurve,
@@ -10,6 +13,10 @@ pub struct ECDSA {
 }
 
 impl ECDSA {
+    pub fn new(ec: EllipticCurve, g: Point, q: BigUint) -> Self {
+        ECDSA { ec, g, q }
+    }
+
     // generate Private key and Public Key pair
     pub fn generate_key_pair(&self) -> (BigUint, Point) {
         let priv_key = self.generate_priv_key();
@@ -22,7 +29,9 @@ impl ECDSA {
     }
 
     pub fn generate_pub_key(&self, priv_key: &BigUint) -> Point {
-        self.ec.scalar_mul(&self.g, priv_key)
+        self.ec
+            .scalar_mul_ct(&self.g, priv_key, self.q.bits())
+            .expect("g is always on the curve")
     }
 
     pub fn generate_random_number(&self) -> BigUint {
@@ -30,36 +39,132 @@ impl ECDSA {
       rng.gen_biguint_range(&BigUint::from(1u32), &self.q)
     }
 
-    pub fn sign(&self, hash: &BigUint, priv_key: BigUint, k: BigUint) -> (BigUint, BigUint) {
-        assert!(*hash < self.q, "hash must be less than q");
-        assert!(priv_key < self.q, "priv_key must be less than q");
-        assert!(k < self.q, "rundom number k must be less than q");
-        let r_point = self.ec.scalar_mul(&self.g, &k);
+    pub fn sign(
+        &self,
+        hash: &BigUint,
+        priv_key: BigUint,
+        k: BigUint,
+    ) -> Result<(BigUint, BigUint), EcError> {
+        if *hash >= self.q {
+            return Err(EcError::HashOutOfRange);
+        }
+        if priv_key >= self.q || k >= self.q {
+            return Err(EcError::ScalarOutOfRange);
+        }
+        let r_point = self.ec.scalar_mul_ct(&self.g, &k, self.q.bits())?;
         if let Point::Coor(r, _) = r_point {
             let s = FiniteField::mult(&r, &priv_key, &self.q);
-            let s = FiniteField::add(&s, &hash, &self.q);
-            let k_inv = FiniteField::inv_add(&k, &self.q);
+            let s = FiniteField::add(&s, hash, &self.q);
+            let k_inv = FiniteField::inv_mult(&k, &self.q);
             let s = FiniteField::mult(&s, &k_inv, &self.q);
-            return (r, s);
+            if r == BigUint::from(0u32) || s == BigUint::from(0u32) {
+                return Err(EcError::ZeroSignatureComponent);
+            }
+            return Ok((r, s));
         }
-        panic!("r_point should not be Identity")
+        Err(EcError::InfinityResult)
     }
 
-    pub fn verify(&self, hash: &BigUint, pub_key: Point, signature: &(BigUint, BigUint)) -> bool {
-        assert!(*hash < self.q, "hash must be less than q");
+    pub fn verify(
+        &self,
+        hash: &BigUint,
+        pub_key: Point,
+        signature: &(BigUint, BigUint),
+    ) -> Result<bool, EcError> {
+        if *hash >= self.q {
+            return Err(EcError::HashOutOfRange);
+        }
         let (r, s) = signature;
-        let s_inv = FiniteField::inv_mult(&s, &self.q);
+        if *r == BigUint::from(0u32) || *s == BigUint::from(0u32) {
+            return Err(EcError::ZeroSignatureComponent);
+        }
+        let s_inv = FiniteField::inv_mult(s, &self.q);
         let u1 = FiniteField::mult(&s_inv, hash, &self.q);
         let u2 = FiniteField::mult(&s_inv, r, &self.q);
-        let u1a = self.ec.scalar_mul(&self.g, &u1);
-        let u1b = self.ec.scalar_mul(&pub_key, &u2);
-        let p = self.ec.add(&u1a, &u1b);
-        println!("p: {:?}", p);
+        let u1a = self.ec.scalar_mul(&self.g, &u1)?;
+        let u1b = self.ec.scalar_mul(&pub_key, &u2)?;
+        let p = self.ec.add(&u1a, &u1b)?;
+
+        let rlen = (self.ec.p.bits() as usize).div_ceil(8);
+        if bool::from(p.ct_eq(&Point::Identity, rlen)) {
+            return Ok(false);
+        }
         if let Point::Coor(xp, _) = p {
-            return xp == *r;
+            return Ok(xp == *r);
         }
 
-        panic!("p should not be Identity")
+        unreachable!("p was checked to not be Identity")
+    }
+
+    // RFC 6979: derive k deterministically from the hash and private key so
+    // a weak or reused nonce can never leak priv_key, as a caller-supplied k would.
+    pub fn sign_deterministic(
+        &self,
+        hash: &BigUint,
+        priv_key: BigUint,
+    ) -> Result<(BigUint, BigUint), EcError> {
+        if *hash >= self.q {
+            return Err(EcError::HashOutOfRange);
+        }
+        if priv_key >= self.q {
+            return Err(EcError::ScalarOutOfRange);
+        }
+
+        let qlen = self.q.bits() as usize;
+        let rlen = qlen.div_ceil(8);
+        let priv_bytes = Self::int_to_octets(&priv_key, rlen);
+        let hash_bytes = Self::int_to_octets(hash, rlen);
+
+        let mut v = vec![0x01u8; rlen];
+        let mut k = vec![0x00u8; rlen];
+
+        k = Self::hmac(&k, &[v.clone(), vec![0x00], priv_bytes.clone(), hash_bytes.clone()].concat());
+        v = Self::hmac(&k, &v);
+        k = Self::hmac(&k, &[v.clone(), vec![0x01], priv_bytes, hash_bytes].concat());
+        v = Self::hmac(&k, &v);
+
+        loop {
+            v = Self::hmac(&k, &v);
+            let candidate = Self::bits_to_int(&v, qlen);
+            if candidate >= BigUint::from(1u32) && candidate < self.q {
+                match self.sign(hash, priv_key.clone(), candidate) {
+                    Ok(signature) => return Ok(signature),
+                    Err(EcError::ZeroSignatureComponent) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            k = Self::hmac(&k, &[v.clone(), vec![0x00]].concat());
+            v = Self::hmac(&k, &v);
+        }
+    }
+
+    fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts a key of any size");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    // left-pad/truncate a value to a fixed `rlen`-byte big-endian string (RFC 6979 int2octets)
+    fn int_to_octets(x: &BigUint, rlen: usize) -> Vec<u8> {
+        let bytes = x.to_bytes_be();
+        if bytes.len() >= rlen {
+            bytes[bytes.len() - rlen..].to_vec()
+        } else {
+            let mut padded = vec![0u8; rlen - bytes.len()];
+            padded.extend(bytes);
+            padded
+        }
+    }
+
+    // interpret `v` as an integer truncated to its leftmost `qlen` bits (RFC 6979 bits2int)
+    fn bits_to_int(v: &[u8], qlen: usize) -> BigUint {
+        let vlen = v.len() * 8;
+        let value = BigUint::from_bytes_be(v);
+        if vlen > qlen {
+            value >> (vlen - qlen)
+        } else {
+            value
+        }
     }
 
     pub fn generate_hash(&self, message: &str) -> BigUint {
@@ -67,8 +172,7 @@ impl ECDSA {
         let hash_bytes = hex::decode(digest).expect("Decoding failed");
         let hash = BigUint::from_bytes_be(&hash_bytes);
         let hash = hash.modpow(&BigUint::from(1u32), &(&self.q - BigUint::from(1u32)));
-        let hash = hash + BigUint::from(1u32);
-        hash
+        hash + BigUint::from(1u32)
     }
 }
 
@@ -95,10 +199,56 @@ mod test {
 
         let message = "Bob transfer 1 BTC to Alice";
         let hash: BigUint = ecdsa.generate_hash(message);
-        let signature = ecdsa.sign(&hash, priv_key, k);
+        let signature = ecdsa.sign(&hash, priv_key, k).expect("sign should succeed");
         println!("signature: {:?}", signature);
 
         let verify_result = ecdsa.verify(&hash, pub_key, &signature);
-        assert!(verify_result, "verification failed");
+        assert_eq!(verify_result, Ok(true), "verification failed");
+    }
+
+    #[test]
+    fn test_sign_deterministic_verify() {
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let g = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+        let q = BigUint::from(19u32);
+
+        let ecdsa = ECDSA { ec, g, q };
+
+        let priv_key = BigUint::from(7u32);
+        let pub_key = ecdsa.generate_pub_key(&priv_key);
+
+        let message = "Bob transfer 1 BTC to Alice";
+        let hash: BigUint = ecdsa.generate_hash(message);
+        let signature = ecdsa
+            .sign_deterministic(&hash, priv_key.clone())
+            .expect("sign_deterministic should succeed");
+
+        let verify_result = ecdsa.verify(&hash, pub_key, &signature);
+        assert_eq!(verify_result, Ok(true), "verification failed");
+
+        // determinism: signing the same message with the same key twice yields the same (r, s)
+        let signature_again = ecdsa
+            .sign_deterministic(&hash, priv_key)
+            .expect("sign_deterministic should succeed");
+        assert_eq!(signature, signature_again);
+    }
+
+    #[test]
+    fn test_sign_rejects_hash_out_of_range() {
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let g = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+        let q = BigUint::from(19u32);
+        let ecdsa = ECDSA { ec, g, q: q.clone() };
+
+        let result = ecdsa.sign(&q, BigUint::from(7u32), BigUint::from(18u32));
+        assert_eq!(result, Err(EcError::HashOutOfRange));
     }
 }
\ No newline at end of file
@@ -0,0 +1,3 @@
+pub mod curves;
+pub mod ecdsa;
+pub mod utils;
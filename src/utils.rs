@@ -1,10 +1,78 @@
 use num_bigint::BigUint;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Point {
     Coor(BigUint, BigUint),
     Identity,
 }
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum EcError {
+    HashOutOfRange,
+    ScalarOutOfRange,
+    PointNotOnCurve,
+    PointsEqual,
+    ZeroSignatureComponent,
+    InfinityResult,
+    NoSquareRoot,
+    InvalidEncoding,
+}
+
+impl Point {
+    // constant-time equality, for use where branching on a point leaks secret-dependent timing
+    pub fn ct_eq(&self, other: &Self, field_bytes: usize) -> Choice {
+        let (id_a, xa, ya) = point_to_ct_limbs(self, field_bytes);
+        let (id_b, xb, yb) = point_to_ct_limbs(other, field_bytes);
+        id_a.ct_eq(&id_b) & xa.ct_eq(&xb) & ya.ct_eq(&yb)
+    }
+}
+
+// fixed-width big-endian encoding used by the constant-time helpers below
+fn fixed_width_bytes(x: &BigUint, rlen: usize) -> Vec<u8> {
+    let raw = x.to_bytes_be();
+    let mut out = vec![0u8; rlen];
+    out[rlen - raw.len()..].copy_from_slice(&raw);
+    out
+}
+
+fn point_to_ct_limbs(p: &Point, rlen: usize) -> (Choice, Vec<u8>, Vec<u8>) {
+    match p {
+        Point::Identity => (Choice::from(1u8), vec![0u8; rlen], vec![0u8; rlen]),
+        Point::Coor(x, y) => (
+            Choice::from(0u8),
+            fixed_width_bytes(x, rlen),
+            fixed_width_bytes(y, rlen),
+        ),
+    }
+}
+
+fn point_from_ct_limbs(is_identity: Choice, x: &[u8], y: &[u8]) -> Point {
+    if bool::from(is_identity) {
+        Point::Identity
+    } else {
+        Point::Coor(BigUint::from_bytes_be(x), BigUint::from_bytes_be(y))
+    }
+}
+
+// swaps r0 and r1 in constant time (the swap decision is hidden; the point
+// arithmetic performed around the swap in scalar_mul_ct is not).
+fn conditional_swap_points(r0: &mut Point, r1: &mut Point, choice: Choice, rlen: usize) {
+    let (id0, mut x0, mut y0) = point_to_ct_limbs(r0, rlen);
+    let (id1, mut x1, mut y1) = point_to_ct_limbs(r1, rlen);
+
+    let mut id0b = id0.unwrap_u8();
+    let mut id1b = id1.unwrap_u8();
+    u8::conditional_swap(&mut id0b, &mut id1b, choice);
+
+    for i in 0..rlen {
+        u8::conditional_swap(&mut x0[i], &mut x1[i], choice);
+        u8::conditional_swap(&mut y0[i], &mut y1[i], choice);
+    }
+
+    *r0 = point_from_ct_limbs(Choice::from(id0b), &x0, &y0);
+    *r1 = point_from_ct_limbs(Choice::from(id1b), &x1, &y1);
+}
 pub struct EllipticCurve {
     // y^1 = x^2 + a*x + b
     pub a: BigUint,
@@ -13,17 +81,18 @@ pub struct EllipticCurve {
 }
 
 impl EllipticCurve {
-    pub fn add(&self, c: &Point, d: &Point) -> Point {
-        assert!(self.is_on_curve(c), "p must be on the curve");
-        assert!(self.is_on_curve(d), "q must be on the curve");
-        assert!(*c != *d, "p and q must be different");
+    pub fn add(&self, c: &Point, d: &Point) -> Result<Point, EcError> {
+        if !self.is_on_curve(c) || !self.is_on_curve(d) {
+            return Err(EcError::PointNotOnCurve);
+        }
         match (c, d) {
-            (Point::Identity, d) => d.clone(),
-            (c, Point::Identity) => c.clone(),
+            (Point::Identity, d) => Ok(d.clone()),
+            (c, Point::Identity) => Ok(c.clone()),
+            (c, d) if c == d => self.double(c),
             (Point::Coor(x1, y1), Point::Coor(x2, y2)) => {
                 let y1plusy2 = FiniteField::add(y1, y2, &self.p);
                 if x1 == x2 && y1plusy2 == BigUint::from(0u32) {
-                    return Point::Identity;
+                    return Ok(Point::Identity);
                 }
                 // s = (y2 - y1) /( x2 - x1)
                 // x3 = s^2 - x1 - x2
@@ -35,8 +104,8 @@ impl EllipticCurve {
 
                 let s2 = s.modpow(&BigUint::from(2u32), &self.p);
                 let x3 = FiniteField::substract(
-                    &FiniteField::substract(&s2, &x1, &self.p),
-                    &x2,
+                    &FiniteField::substract(&s2, x1, &self.p),
+                    x2,
                     &self.p,
                 );
                 let y3 = FiniteField::substract(
@@ -44,15 +113,17 @@ impl EllipticCurve {
                     y1,
                     &self.p,
                 );
-                Point::Coor(x3, y3)
+                Ok(Point::Coor(x3, y3))
             }
         }
     }
 
-    fn double(&self, p: &Point) -> Point {
-        assert!(self.is_on_curve(p), "p must be on the curve");
+    fn double(&self, p: &Point) -> Result<Point, EcError> {
+        if !self.is_on_curve(p) {
+            return Err(EcError::PointNotOnCurve);
+        }
         match p {
-            Point::Identity => Point::Identity,
+            Point::Identity => Ok(Point::Identity),
             Point::Coor(x, y) => {
                 // s = (3x^2 + a) / (2y)
                 // x3 = s^2 - 2x
@@ -61,40 +132,113 @@ impl EllipticCurve {
                 let s_u = FiniteField::mult(&BigUint::from(3u32), &x2, &self.p);
                 let s_u = FiniteField::add(&s_u, &self.a, &self.p);
 
-                let s_b = FiniteField::mult(&y, &BigUint::from(2u32), &self.p);
+                let s_b = FiniteField::mult(y, &BigUint::from(2u32), &self.p);
 
                 let s = FiniteField::divide(&s_u, &s_b, &self.p);
                 let s2 = s.modpow(&BigUint::from(2u32), &self.p);
                 let x3 = FiniteField::substract(
                     &s2,
-                    &FiniteField::mult(&x, &BigUint::from(2u32), &self.p),
+                    &FiniteField::mult(x, &BigUint::from(2u32), &self.p),
                     &self.p,
                 );
                 let y3 = FiniteField::substract(
-                    &FiniteField::mult(&s, &FiniteField::substract(&x, &x3, &self.p), &self.p),
-                    &y,
+                    &FiniteField::mult(&s, &FiniteField::substract(x, &x3, &self.p), &self.p),
+                    y,
                     &self.p,
                 );
 
-                Point::Coor(x3, y3)
+                Ok(Point::Coor(x3, y3))
             }
         }
     }
-    pub fn scalar_mul(&self, p: &Point, d: &BigUint) -> Point {
+    pub fn scalar_mul(&self, p: &Point, d: &BigUint) -> Result<Point, EcError> {
         // double and add algorithm
         // B = d * A
         let mut t = p.clone();
-        if *d == BigUint::from(0u32){ 
-          return Point::Identity; 
-      }
+        if *d == BigUint::from(0u32) {
+            return Ok(Point::Identity);
+        }
         for i in (0..(d.bits() - 1)).rev() {
-            t = self.double(&t);
+            t = self.double(&t)?;
             if d.bit(i) {
-                t = self.add(&t, &p);
+                t = self.add(&t, p)?;
+            }
+        }
+        Ok(t)
+    }
+    // SEC1 compressed encoding: a 0x02/0x03 parity prefix followed by big-endian x,
+    // or a single 0x00 byte for the point at infinity.
+    pub fn point_to_bytes(&self, point: &Point) -> Vec<u8> {
+        match point {
+            Point::Identity => vec![0x00],
+            Point::Coor(x, y) => {
+                let rlen = (self.p.bits() as usize).div_ceil(8);
+                let prefix = if y.bit(0) { 0x03 } else { 0x02 };
+                let x_bytes = x.to_bytes_be();
+                let mut bytes = vec![0u8; rlen + 1];
+                bytes[0] = prefix;
+                bytes[1 + rlen - x_bytes.len()..].copy_from_slice(&x_bytes);
+                bytes
             }
         }
-        t
     }
+
+    pub fn point_from_bytes(&self, bytes: &[u8]) -> Result<Point, EcError> {
+        if bytes == [0x00] {
+            return Ok(Point::Identity);
+        }
+        let (prefix, x_bytes) = bytes.split_first().ok_or(EcError::InvalidEncoding)?;
+        if *prefix != 0x02 && *prefix != 0x03 {
+            return Err(EcError::InvalidEncoding);
+        }
+
+        let x = BigUint::from_bytes_be(x_bytes);
+        if x >= self.p {
+            return Err(EcError::InvalidEncoding);
+        }
+        let x3 = x.modpow(&BigUint::from(3u32), &self.p);
+        let ax = FiniteField::mult(&self.a, &x, &self.p);
+        let rhs = FiniteField::add(&FiniteField::add(&x3, &ax, &self.p), &self.b, &self.p);
+
+        let y = FiniteField::sqrt(&rhs, &self.p).ok_or(EcError::NoSquareRoot)?;
+        let wants_odd = *prefix == 0x03;
+        let y = if y.bit(0) == wants_odd {
+            y
+        } else {
+            FiniteField::inv_add(&y, &self.p)
+        };
+
+        Ok(Point::Coor(x, y))
+    }
+
+    // Montgomery-ladder scalar multiplication. Both the addition and the doubling
+    // run on every iteration regardless of the bit; only a constant-time conditional
+    // swap (subtle) picks which accumulator holds which value, and the loop always
+    // runs `bit_length` times. The point arithmetic itself is not constant-time:
+    // `add` and `double` return early while `r0` is the identity (one iteration per
+    // leading zero bit of `d` below `bit_length`), and BigUint arithmetic takes
+    // operand-dependent time, so runtime still varies with `d`.
+    pub fn scalar_mul_ct(&self, p: &Point, d: &BigUint, bit_length: u64) -> Result<Point, EcError> {
+        let rlen = (self.p.bits() as usize).div_ceil(8);
+
+        let mut r0 = Point::Identity;
+        let mut r1 = p.clone();
+        let mut swap = Choice::from(0u8);
+
+        for i in (0..bit_length).rev() {
+            let bit = Choice::from(d.bit(i) as u8);
+            let swap_now = Choice::from(swap.unwrap_u8() ^ bit.unwrap_u8());
+            conditional_swap_points(&mut r0, &mut r1, swap_now, rlen);
+            swap = bit;
+
+            let sum = self.add(&r0, &r1)?;
+            r0 = self.double(&r0)?;
+            r1 = sum;
+        }
+        conditional_swap_points(&mut r0, &mut r1, swap, rlen);
+        Ok(r0)
+    }
+
     fn is_on_curve(&self, p: &Point) -> bool {
         // y^2 = x^3 + ax + b
         match p {
@@ -109,6 +253,41 @@ impl EllipticCurve {
     }
 }
 
+/// Wraps a `Point` together with the curve it belongs to, so that `+` and `*`
+/// can be used in place of `ec.add(..)` / `ec.scalar_mul(..)`.
+pub struct CurvePoint<'a> {
+    pub curve: &'a EllipticCurve,
+    pub point: Point,
+}
+
+impl<'a> CurvePoint<'a> {
+    pub fn new(curve: &'a EllipticCurve, point: Point) -> Self {
+        CurvePoint { curve, point }
+    }
+}
+
+impl<'a> std::ops::Add for &CurvePoint<'a> {
+    type Output = CurvePoint<'a>;
+    fn add(self, rhs: &CurvePoint<'a>) -> CurvePoint<'a> {
+        let sum = self
+            .curve
+            .add(&self.point, &rhs.point)
+            .expect("CurvePoint operands must be valid points on the curve");
+        CurvePoint::new(self.curve, sum)
+    }
+}
+
+impl<'a> std::ops::Mul<&BigUint> for &CurvePoint<'a> {
+    type Output = CurvePoint<'a>;
+    fn mul(self, scalar: &BigUint) -> CurvePoint<'a> {
+        let product = self
+            .curve
+            .scalar_mul(&self.point, scalar)
+            .expect("CurvePoint operand must be a valid point on the curve");
+        CurvePoint::new(self.curve, product)
+    }
+}
+
 pub struct FiniteField {}
 
 impl FiniteField {
@@ -140,13 +319,78 @@ impl FiniteField {
         // c^-1 mod p = c^(p-2) mod p
         // Fermat's little theorem
         // c^(p-1) mod p = 1
-        c.modpow(&(p - BigUint::from(2u32)), &p)
+        //
+        // NOTE: BigUint::modpow is not constant-time in its exponent, so this
+        // leaks `c` (e.g. the `s` inverted in `verify`, or the nonce `k`
+        // inverted in `sign`) through timing. scalar_mul_ct also reaches this
+        // function through `add` and `double`.
+        c.modpow(&(p - BigUint::from(2u32)), p)
     }
 
     pub fn divide(c: &BigUint, d: &BigUint, p: &BigUint) -> BigUint {
         let d_inv = FiniteField::inv_mult(d, p);
         FiniteField::mult(c, &d_inv, p)
     }
+
+    // modular square root mod p, or None if `a` is not a quadratic residue
+    pub fn sqrt(a: &BigUint, p: &BigUint) -> Option<BigUint> {
+        let zero = BigUint::from(0u32);
+        let one = BigUint::from(1u32);
+        let two = BigUint::from(2u32);
+
+        if *a == zero {
+            return Some(zero);
+        }
+
+        // Euler's criterion
+        if a.modpow(&((p - &one) / &two), p) != one {
+            return None;
+        }
+
+        if (p % &BigUint::from(4u32)) == BigUint::from(3u32) {
+            return Some(a.modpow(&((p + &one) / &BigUint::from(4u32)), p));
+        }
+
+        // Tonelli-Shanks: write p - 1 = q * 2^s with q odd
+        let mut q = p - &one;
+        let mut s: u32 = 0;
+        while (&q % &two) == zero {
+            q = &q / &two;
+            s += 1;
+        }
+
+        // find a quadratic non-residue z
+        let mut z = two.clone();
+        while z.modpow(&((p - &one) / &two), p) != (p - &one) {
+            z = &z + &one;
+        }
+
+        let mut m = s;
+        let mut c = z.modpow(&q, p);
+        let mut t = a.modpow(&q, p);
+        let mut r = a.modpow(&((&q + &one) / &two), p);
+
+        loop {
+            if t == one {
+                return Some(r);
+            }
+            // find the least i, 0 < i < m, such that t^(2^i) == 1
+            let mut i: u32 = 0;
+            let mut t2i = t.clone();
+            while t2i != one {
+                t2i = FiniteField::mult(&t2i, &t2i, p);
+                i += 1;
+                if i == m {
+                    return None;
+                }
+            }
+            let b = c.modpow(&two.pow(m - i - 1), p);
+            m = i;
+            c = FiniteField::mult(&b, &b, p);
+            t = FiniteField::mult(&t, &c, p);
+            r = FiniteField::mult(&r, &b, p);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -218,9 +462,126 @@ mod test {
         let p1 = Point::Coor(BigUint::from(6u32), BigUint::from(3u32));
         let p2 = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
         let pr = Point::Coor(BigUint::from(10u32), BigUint::from(6u32));
-        let res = ec.add(&p1, &p2);
+        let res = ec.add(&p1, &p2).unwrap();
         assert_eq!(res, pr);
     }
+    #[test]
+    fn test_ec_point_addition_same_point_delegates_to_double() {
+        //y^2 = x^3 + 2x + 2 mod 17
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let p1 = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+        let res = ec.add(&p1, &p1).unwrap();
+        assert_eq!(res, ec.scalar_mul(&p1, &BigUint::from(2u32)).unwrap());
+    }
+
+    #[test]
+    fn test_ec_add_rejects_point_not_on_curve() {
+        //y^2 = x^3 + 2x + 2 mod 17
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let off_curve = Point::Coor(BigUint::from(2u32), BigUint::from(2u32));
+        let on_curve = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+        assert_eq!(ec.add(&off_curve, &on_curve), Err(EcError::PointNotOnCurve));
+    }
+
+    #[test]
+    fn test_curve_point_operators() {
+        //y^2 = x^3 + 2x + 2 mod 17
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let p1 = CurvePoint::new(&ec, Point::Coor(BigUint::from(6u32), BigUint::from(3u32)));
+        let p2 = CurvePoint::new(&ec, Point::Coor(BigUint::from(5u32), BigUint::from(1u32)));
+
+        let sum = &p1 + &p2;
+        assert_eq!(sum.point, Point::Coor(BigUint::from(10u32), BigUint::from(6u32)));
+
+        let g = CurvePoint::new(&ec, Point::Coor(BigUint::from(5u32), BigUint::from(1u32)));
+        let doubled = &g * &BigUint::from(2u32);
+        assert_eq!(doubled.point, Point::Coor(BigUint::from(6u32), BigUint::from(3u32)));
+    }
+
+    #[test]
+    fn test_point_compressed_round_trip() {
+        //y^2 = x^3 + 2x + 2 mod 17
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let g = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+
+        let bytes = ec.point_to_bytes(&g);
+        let decoded = ec.point_from_bytes(&bytes).expect("g should decode");
+        assert_eq!(decoded, g);
+
+        let identity_bytes = ec.point_to_bytes(&Point::Identity);
+        let decoded_identity = ec
+            .point_from_bytes(&identity_bytes)
+            .expect("identity should decode");
+        assert_eq!(decoded_identity, Point::Identity);
+    }
+
+    #[test]
+    fn test_point_from_bytes_rejects_non_residue() {
+        //y^2 = x^3 + 2x + 2 mod 17, x=2 is not on the curve
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let bytes = vec![0x02, 2u8];
+        assert_eq!(ec.point_from_bytes(&bytes), Err(EcError::NoSquareRoot));
+    }
+
+    #[test]
+    fn test_point_from_bytes_rejects_x_out_of_range() {
+        //y^2 = x^3 + 2x + 2 mod 17, x=17 is not a valid field element
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let bytes = vec![0x02, 17u8];
+        assert_eq!(ec.point_from_bytes(&bytes), Err(EcError::InvalidEncoding));
+    }
+
+    #[test]
+    fn test_ec_scalar_ct_matches_variable_time() {
+        //y^2 = x^3 + 2x + 2 mod 17
+        let ec = EllipticCurve {
+            a: BigUint::from(2u32),
+            b: BigUint::from(2u32),
+            p: BigUint::from(17u32),
+        };
+        let p1 = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+        let d = BigUint::from(7u32);
+
+        let res = ec.scalar_mul_ct(&p1, &d, 19u64.max(d.bits())).unwrap();
+        assert_eq!(res, ec.scalar_mul(&p1, &d).unwrap());
+    }
+
+    #[test]
+    fn test_point_ct_eq() {
+        let a = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+        let b = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
+        let c = Point::Coor(BigUint::from(6u32), BigUint::from(3u32));
+
+        assert!(bool::from(a.ct_eq(&b, 1)));
+        assert!(!bool::from(a.ct_eq(&c, 1)));
+        assert!(!bool::from(a.ct_eq(&Point::Identity, 1)));
+        assert!(bool::from(Point::Identity.ct_eq(&Point::Identity, 1)));
+    }
+
     #[test]
     fn test_ec_scalar() {
         //y^2 = x^3 + 2x + 2 mod 17
@@ -233,7 +594,7 @@ mod test {
         let p1 = Point::Coor(BigUint::from(5u32), BigUint::from(1u32));
         let p2 = Point::Coor(BigUint::from(6u32), BigUint::from(3u32));
 
-        let res = ec.scalar_mul(&p1, &BigUint::from(2u32));
+        let res = ec.scalar_mul(&p1, &BigUint::from(2u32)).unwrap();
         assert_eq!(res, p2);
     }
 }